@@ -1,37 +1,24 @@
-//! Implementation of `derive(Rand)` for `custom_derive!{}`.
+//! Implementation of `#[derive(Rand)]` and `#[derive(Arbitrary)]`.
 //!
-//! This crate defines a macro `Rand!{}` that can be used through `custom_derive!{}`
-//! to derive an implementation of the `Rand` trait (crate rand version 0.3.x).
-//!
-//! Using this macro also depends on crates `parse_macros` and `parse_generics_shim`,
-//! which must be included in the crate that uses them.
-//!
-//! ## Example
-//!
-//! ```
-//! extern crate rand;
-//!
-//! #[macro_use] extern crate parse_macros;
-//! #[macro_use] extern crate parse_generics_shim;
-//! #[macro_use] extern crate custom_derive;
+//! This crate provides procedural macros that derive implementations of the
+//! `Rand` trait (crate rand version 0.3.x) and of `quickcheck::Arbitrary` for
+//! structs and enums:
 //!
+//! ```ignore
 //! #[macro_use] extern crate rand_derive;
+//! extern crate rand;
 //!
-//! custom_derive! {
-//!     #[derive(Rand, Debug)]
-//!     enum TestEnum {
-//!         A,
-//!         B,
-//!         C,
-//!     }
+//! #[derive(Rand, Debug)]
+//! enum TestEnum {
+//!     A,
+//!     B,
+//!     C,
 //! }
 //!
-//! custom_derive! {
-//!     #[derive(Rand, Debug)]
-//!     struct Point<T> {
-//!         x: T,
-//!         y: T,
-//!     }
+//! #[derive(Rand, Debug)]
+//! struct Point<T> {
+//!     x: T,
+//!     y: T,
 //! }
 //!
 //! fn main() {
@@ -40,347 +27,402 @@
 //! }
 //! ```
 //!
-//! ## Known Limitations
+//! The `Rand` derive emits `impl Rand for T { fn rand<R: Rng>(rng) }`. Without
+//! attributes it selects enum variants uniformly and fills every field with
+//! `rng.gen()`; see the attributes below for the exceptions. Unlike the older `custom_derive!`-based `Rand!{}` macro it
+//! participates in ordinary `#[derive(...)]` lists and is not subject to the
+//! macro recursion limit.
 //!
-//! If the struct or enum is too complex, the compiler may run up against
-//! the recursion limit when compiling your crate. This can be adjusted
-//! with an attribute like `#![recursion_limit="128"]`.
+//! ## Attributes
 //!
-//! * Does not allow explicit discriminants on unitary enum variants
-//! * Does not yet allow customizing which type parameters get the `T: Rand`
-//!   bound applied. Right now they all get it.
-#![cfg_attr(not(test), no_std)]
-
-//#![cfg_attr(test, feature(trace_macros))]
-#![recursion_limit="128"]
-#[cfg(test)]
-#[macro_use] extern crate parse_macros;
-#[cfg(test)]
-#[macro_use] extern crate parse_generics_shim;
-#[cfg(test)]
-#[macro_use] extern crate custom_derive;
-#[cfg(test)]
-extern crate rand;
-
-/// Implementation of `derive(Rand)` for `custom_derive!{}`.
-#[macro_export]
-macro_rules! Rand {
-    (
-        () $($tail:tt)*
-    ) => {
-        parse_item! {
-            then Rand! { @item },
-            $($tail)*
-        }
+//! By default every type parameter gets a `T: Rand` bound and every field is
+//! filled with `rng.gen()`. This can be customized with `#[rand(...)]`:
+//!
+//! * `#[rand(bound = "T: MyTrait")]` on a type parameter replaces the
+//!   auto-generated `T: Rand` clause for that parameter.
+//! * `#[rand(skip)]` or `#[rand(default)]` on a field fills it with
+//!   `Default::default()` instead of a random value (useful for `PhantomData`
+//!   or cache fields).
+//! * `#[rand(with = "path::to_fn")]` on a field calls `path::to_fn(rng)` to
+//!   produce the value, where the function has signature `fn<R: Rng>(&mut R) ->
+//!   FieldTy`.
+//! * `#[rand(weight = N)]` on an enum variant samples that variant
+//!   proportionally to `N` (default 1) instead of uniformly.
+//!
+//! Explicit discriminants on unitary enum variants (`enum E { A = 1, B = 4 }`)
+//! are accepted; variants are still selected by their declaration order, so the
+//! discriminant values do not change the distribution.
+
+extern crate proc_macro;
+extern crate proc_macro2;
+extern crate darling;
+#[macro_use] extern crate quote;
+extern crate syn;
+
+use darling::{FromField, FromTypeParam, FromVariant};
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use syn::punctuated::Punctuated;
+use syn::token::Comma;
+use syn::{Data, DeriveInput, Field, Fields, GenericParam, Ident, WherePredicate};
+
+/// Per-field `#[rand(...)]` options.
+#[derive(Default, FromField)]
+#[darling(attributes(rand), default)]
+struct FieldOpts {
+    skip: bool,
+    default: bool,
+    with: Option<String>,
+}
+
+/// Per-variant `#[rand(...)]` options.
+#[derive(FromVariant)]
+#[darling(attributes(rand))]
+struct VariantOpts {
+    #[darling(default = "default_weight")]
+    weight: u64,
+}
+
+fn default_weight() -> u64 {
+    1
+}
+
+/// Per-type-parameter `#[rand(...)]` options.
+#[derive(Default, FromTypeParam)]
+#[darling(attributes(rand), default)]
+struct ParamOpts {
+    bound: Option<String>,
+}
+
+/// Derive an implementation of `rand::Rand`.
+#[proc_macro_derive(Rand, attributes(rand))]
+pub fn derive_rand(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = syn::parse(input).expect("derive(Rand): failed to parse input");
+    expand_rand(&input).into()
+}
+
+/// Derive an implementation of `quickcheck::Arbitrary`.
+#[proc_macro_derive(Arbitrary, attributes(rand))]
+pub fn derive_arbitrary(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = syn::parse(input).expect("derive(Arbitrary): failed to parse input");
+    expand_arbitrary(&input).into()
+}
+
+fn expand_rand(input: &DeriveInput) -> TokenStream2 {
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    let where_clause = build_where_clause(&mut generics, &quote!(::rand::Rand), true);
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+
+    let body = match input.data {
+        Data::Struct(ref data) => construct(quote!(#name), &data.fields),
+        Data::Enum(ref data) => rand_enum(name, data),
+        Data::Union(_) => panic!("derive(Rand) does not support unions"),
     };
-    // enum
-    (
-        @item
-        enum {
-            attrs: $_attrs:tt,
-            vis: $_vis:tt,
-            name: $name:ident,
-            generics: {
-                constr: [$($constr:tt)*],
-                params: [$($params:tt)*],
-                ltimes: $_ltimes:tt,
-                tnames: [$($tnames:ident,)*],
-            },
-            where: {
-                clause: $_clause:tt,
-                preds: [$($preds:tt)*],
-            },
-            variants: [
-                $({
-                    ord: ($ord:expr, $_ord:tt),
-                    attrs: [$($_vattrs:tt)*],
-                    kind: $vkind:ident,
-                    name: $vname:ident,
-                    fields: $vfields:tt,
-                    num_fields: $vnum_fields:expr,
-                },)+  // + because 0 variants is explicitly unsupported
-            ],
-            num_variants: $num_variants:expr,
-            $($_enum_tail:tt)*
-        }
-    ) => {
-        Rand!{ @inject_where
-            [$($tnames: ::rand::Rand,)* $($preds)*]
-            [impl<$($constr)*> ::rand::Rand for $name<$($params)*>]
-            {
-                fn rand<R: ::rand::Rng>(_rng: &mut R) -> Self {
-                    let variant = Rand!(
-                        @isone [$($vname)*]
-                        0,
-                        _rng.gen_range(0, $num_variants));
-                    match variant {
-                    $(
-                        $ord => Rand!(@enum $vkind _rng $name $vname $vfields),
-                    )+
-                        _ => loop { }
-                    }
-                }
+
+    quote! {
+        impl #impl_generics ::rand::Rand for #name #ty_generics #where_clause {
+            fn rand<R: ::rand::Rng>(_rng: &mut R) -> Self {
+                #body
             }
         }
+    }
+}
+
+fn expand_arbitrary(input: &DeriveInput) -> TokenStream2 {
+    let name = &input.ident;
+    let mut generics = input.generics.clone();
+    let where_clause =
+        build_where_clause(&mut generics, &quote!(::quickcheck::Arbitrary), false);
+    let (impl_generics, ty_generics, _) = generics.split_for_impl();
+
+    let (arbitrary, shrink) = match input.data {
+        Data::Struct(ref data) => (
+            arbitrary_construct(quote!(#name), &data.fields),
+            arbitrary_shrink_struct(name, &data.fields),
+        ),
+        Data::Enum(ref data) => (arbitrary_enum(name, data), arbitrary_shrink_enum(name, data)),
+        Data::Union(_) => panic!("derive(Arbitrary) does not support unions"),
     };
-    // @isone: test if there is exactly one tt in the list, then $e else $f
-    (@isone [$_one:tt] $e:expr, $_f:expr) => { $e };
-    (@isone [$($_notone:tt)*] $_e:expr, $f:expr) => { $f };
-    (@enum unitary $rng:ident $name:ident $vname:ident $vfields:tt) => {
-        $name::$vname
-    };
-    (@enum tuple $rng:ident $name:ident $vname:ident
-     [$($vfield:tt,)*]
-    ) => {
-        $name::$vname($(Rand!(@sub $vfield $rng.gen())),*)
-    };
-    (@enum record $rng:ident $name:ident $vname:ident
-     [$({
-         ord: $_ford:tt,
-         attrs: $_fattrs:tt,
-         vis: $_fvis:tt,
-         ty: $_fty:ty,
-         name: $fname:ident,
-      },)*]
-    ) => {
-        $name::$vname {
-            $(
-                $fname: $rng.gen()
-            ),*
-        }
-    };
-    // struct
-    (
-        @item
-        struct {
-            attrs: $_attrs:tt,
-            vis: $_vis:tt,
-            name: $name:ident,
-            generics: {
-                constr: [$($constr:tt)*],
-                params: [$($params:tt)*],
-                ltimes: $_ltimes:tt,
-                tnames: [$($tnames:ident,)*],
-            },
-            where: {
-                clause: $_clause:tt,
-                preds: [$($preds:tt)*],
-            },
-            kind: $kind:ident,
-            fields: $fields:tt,
-            $($_struct_tail:tt)*
-        }
-    ) => {
-        Rand!{ @inject_where
-            [$($tnames: ::rand::Rand,)* $($preds)*]
-            [impl<$($constr)*> ::rand::Rand for $name<$($params)*>]
-            {
-                fn rand<R: ::rand::Rng>(_rng: &mut R) -> Self {
-                    Rand!{@struct $kind _rng $name $fields }
-                }
+
+    quote! {
+        impl #impl_generics ::quickcheck::Arbitrary for #name #ty_generics #where_clause {
+            fn arbitrary<G: ::quickcheck::Gen>(g: &mut G) -> Self {
+                #arbitrary
+            }
+
+            fn shrink(&self) -> Box<Iterator<Item = Self>> {
+                #shrink
             }
         }
-    };
-    (@struct unitary $rng:ident $name:ident $vfields:tt) => {
-        $name
-    };
-    (@struct tuple $rng:ident $name:ident
-     [$($vfield:tt,)*]
-    ) => {
-        $name($(Rand!(@sub $vfield $rng.gen())),*)
-    };
-    (@struct record $rng:ident $name:ident
-     [$({
-         ord: $_ford:tt,
-         attrs: $_fattrs:tt,
-         vis: $_fvis:tt,
-         ty: $_fty:ty,
-         name: $fname:ident,
-      },)*]
-    ) => {
-        $name {
-            $(
-                $fname: $rng.gen()
-            ),*
-        }
-    };
-    // substitute
-    (@sub $_input:tt $output:expr) => { $output };
-    (@inject_where [] [$($_impl:tt)*] $body:tt) => {
-        Rand!{@as_item $($_impl)* $body}
-    };
-    (@inject_where [$($clause:tt)*] [$($_impl:tt)*] $body:tt) => {
-        Rand!{@as_item $($_impl)* where $($clause)* $body}
-    };
-    (@as_item $i:item) => { $i };
+    }
 }
 
-#[cfg(test)]
-mod tests {
-    //trace_macros!(true);
-    use rand::random;
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        enum Test {
-            A, B, C,
+/// Build the `where` clause, adding a `default_bound` bound for every type
+/// parameter (or, when `honor_override` is set, the user's
+/// `#[rand(bound = "...")]` clause), and strip the helper attributes so the
+/// impl generics stay valid Rust.
+fn build_where_clause(
+    generics: &mut syn::Generics,
+    default_bound: &TokenStream2,
+    honor_override: bool,
+) -> TokenStream2 {
+    let mut predicates: Punctuated<WherePredicate, Comma> = Punctuated::new();
+    for param in generics.params.iter_mut() {
+        if let GenericParam::Type(ref mut type_param) = *param {
+            let opts = ParamOpts::from_type_param(type_param)
+                .expect("invalid #[rand(...)] on type parameter");
+            let ident = &type_param.ident;
+            let pred: WherePredicate = match opts.bound {
+                Some(ref bound) if honor_override => {
+                    syn::parse_str(bound).expect("invalid `bound` clause")
+                }
+                _ => syn::parse_quote!(#ident: #default_bound),
+            };
+            predicates.push(pred);
+            type_param.attrs.retain(|attr| !attr.path.is_ident("rand"));
         }
     }
-    /*
-       // Does not compile with 0 variants
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        pub enum Test2 {
-        }
+    if let Some(ref clause) = generics.where_clause {
+        predicates.extend(clause.predicates.iter().cloned());
+    }
+    if predicates.is_empty() {
+        quote!()
+    } else {
+        quote!(where #predicates)
     }
-    */
+}
 
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        enum Test1 {
-            A,
+/// Build a value of `path` (a struct name or `Enum::Variant`), filling each
+/// field according to its `#[rand(...)]` options.
+fn construct(path: TokenStream2, fields: &Fields) -> TokenStream2 {
+    match *fields {
+        Fields::Named(ref named) => {
+            let fields = named.named.iter().map(|f| {
+                let name = &f.ident;
+                let value = field_value(f);
+                quote!(#name: #value)
+            });
+            quote!(#path { #(#fields),* })
         }
-    }
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        enum Test2 {
-            A,
-            B,
+        Fields::Unnamed(ref unnamed) => {
+            let fields = unnamed.unnamed.iter().map(field_value);
+            quote!(#path ( #(#fields),* ))
         }
+        Fields::Unit => quote!(#path),
     }
+}
 
-    #[test]
-    fn it_works() {
-        let t: Test = random();
-        println!("{:?}", t);
-        let t1: Test1 = random();
-        println!("{:?}", t1);
-        let t2: Test2 = random();
-        println!("{:?}", t2);
+/// The expression that produces a single field's value.
+fn field_value(field: &Field) -> TokenStream2 {
+    let opts = FieldOpts::from_field(field).expect("invalid #[rand(...)] on field");
+    if opts.skip || opts.default {
+        quote!(Default::default())
+    } else if let Some(ref with) = opts.with {
+        let path: syn::Path = syn::parse_str(with).expect("invalid `with` path");
+        quote!(#path(_rng))
+    } else {
+        quote!(_rng.gen())
     }
+}
 
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        enum Test3 {
-            A(i8),
-            B(Test2),
-        }
-    }
-    #[test]
-    fn enum_tuplevar() {
-        let t: Test3 = random();
-        println!("{:?}", t);
+fn rand_enum(name: &Ident, data: &syn::DataEnum) -> TokenStream2 {
+    if data.variants.is_empty() {
+        // Matches the historical "0 variants unsupported" behavior.
+        return quote!(compile_error!("derive(Rand) requires at least one variant"););
     }
 
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        enum TestS {
-            A { x: u8, y: u8 },
-            B { x: u8, y: u8, z: u8 },
-            C { },
-        }
+    let values: Vec<TokenStream2> = data
+        .variants
+        .iter()
+        .map(|variant| {
+            let vname = &variant.ident;
+            construct(quote!(#name::#vname), &variant.fields)
+        })
+        .collect();
+
+    let weights: Vec<u64> = data
+        .variants
+        .iter()
+        .map(|variant| {
+            VariantOpts::from_variant(variant)
+                .expect("invalid #[rand(...)] on variant")
+                .weight
+        })
+        .collect();
+    let total: u64 = weights.iter().sum();
+    if total == 0 {
+        return quote!(compile_error!("derive(Rand) enum has a total weight of zero"););
     }
-    #[test]
-    fn enum_structvar() {
-        let t: TestS = random();
-        println!("{:?}", t);
+
+    // A single variant is chosen unconditionally, like the old `@isone` path.
+    if values.len() == 1 {
+        let value = &values[0];
+        return quote!(#value);
     }
 
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        enum TestGeneric1<T> where T: ::rand::Rand {
-            A { x: T },
-            B { x: u8, y: u8, z: u8 },
+    // Draw in `0..total` and walk the cumulative weight ranges in order. Each
+    // arm is pre-rendered so `#weights`/`#values` are interpolated once apiece.
+    let arms = weights.iter().zip(&values).map(|(w, v)| {
+        quote! {
+            if _x < #w { return #v; }
+            _x -= #w;
         }
+    });
+    quote! {
+        let mut _x = _rng.gen_range(0, #total);
+        #(#arms)*
+        loop { }
     }
+}
 
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        enum TestGeneric2<T> {
-            A { x: T },
-            B { x: u8, y: u8, z: u8 },
+/// Build a value of `path`, filling each field with `Arbitrary::arbitrary(g)`.
+fn arbitrary_construct(path: TokenStream2, fields: &Fields) -> TokenStream2 {
+    match *fields {
+        Fields::Named(ref named) => {
+            let fields = named.named.iter().map(|f| {
+                let name = &f.ident;
+                quote!(#name: ::quickcheck::Arbitrary::arbitrary(g))
+            });
+            quote!(#path { #(#fields),* })
+        }
+        Fields::Unnamed(ref unnamed) => {
+            let fields = unnamed
+                .unnamed
+                .iter()
+                .map(|_| quote!(::quickcheck::Arbitrary::arbitrary(g)));
+            quote!(#path ( #(#fields),* ))
         }
+        Fields::Unit => quote!(#path),
     }
+}
 
-    #[test]
-    fn enum_generic() {
-        let t: TestGeneric1<TestS> = random();
-        println!("{:?}", t);
-        let s: TestGeneric2<()> = random();
-        println!("{:?}", s);
+fn arbitrary_enum(name: &Ident, data: &syn::DataEnum) -> TokenStream2 {
+    if data.variants.is_empty() {
+        return quote!(compile_error!("derive(Arbitrary) requires at least one variant"););
     }
 
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        struct TestStruct;
-    }
+    let num_variants = data.variants.len();
+    let arms = data.variants.iter().enumerate().map(|(ord, variant)| {
+        let vname = &variant.ident;
+        let value = arbitrary_construct(quote!(#name::#vname), &variant.fields);
+        quote!(#ord => #value)
+    });
 
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        struct TestStruct2 {
-            x: u8,
-            y: (),
+    quote! {
+        match g.gen_range(0, #num_variants) {
+            #(#arms,)*
+            _ => unreachable!(),
         }
     }
+}
 
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        struct TestStruct3(u8, Test1);
+/// `shrink` body for a struct: destructure `self` and shrink its fields.
+fn arbitrary_shrink_struct(name: &Ident, fields: &Fields) -> TokenStream2 {
+    let (pattern, body) = arbitrary_shrink_arm(quote!(#name), fields);
+    quote! {
+        match *self {
+            #pattern => #body,
+        }
     }
+}
 
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        struct TestStruct4<T, U> where T: 'static {
-            x: T,
-            y: U,
+/// `shrink` body for an enum: shrink within the matched variant's fields.
+fn arbitrary_shrink_enum(name: &Ident, data: &syn::DataEnum) -> TokenStream2 {
+    if data.variants.is_empty() {
+        return quote!(::quickcheck::empty_shrinker());
+    }
+    let arms = data.variants.iter().map(|variant| {
+        let vname = &variant.ident;
+        let (pattern, body) = arbitrary_shrink_arm(quote!(#name::#vname), &variant.fields);
+        quote!(#pattern => #body)
+    });
+    quote! {
+        match *self {
+            #(#arms,)*
         }
     }
+}
 
-    #[test]
-    fn struct_simple() {
-        let t: TestStruct = random();
-        println!("{:?}", t);
-        let s: TestStruct2 = random();
-        println!("{:?}", s);
-        let u: TestStruct3 = random();
-        println!("{:?}", u);
-        let v: TestStruct4<TestStruct, TestStruct2> = random();
-        println!("{:?}", v);
-    }
+/// Produce the `(pattern, shrink_expr)` pair for one struct or variant: the
+/// pattern binds every field by reference, and the expression lazily replaces
+/// one field at a time with each of its own shrunk values, cloning the rest.
+fn arbitrary_shrink_arm(path: TokenStream2, fields: &Fields) -> (TokenStream2, TokenStream2) {
+    let bindings: Vec<Ident> = (0..fields.iter().count())
+        .map(|i| Ident::new(&format!("__f{}", i), Span::call_site()))
+        .collect();
 
-    custom_derive! {
-        #[derive(Rand, Debug)]
-        struct BigStruct<T> {
-            a: T,
-            b: (),
-            c: i32,
-            d: i32,
-            e: i32,
-            f: u8,
-            g: u8,
-            h: u8,
-            i: f32,
-            j: f32,
-            k: f32,
-            l: f32,
-            m: f32,
-            n: f64,
-            o: Test,
-            p: Test1,
-            q: TestStruct,
-            r: u8,
-            s: u8,
-            t: u8,
-            u: u8,
-            v: u8,
-            x: u8,
-            y: u8,
-            z: u8,
+    let pattern = match *fields {
+        Fields::Named(ref named) => {
+            let pats = named.named.iter().zip(&bindings).map(|(f, b)| {
+                let name = &f.ident;
+                quote!(#name: ref #b)
+            });
+            quote!(#path { #(#pats),* })
         }
-    }
+        Fields::Unnamed(_) => {
+            let pats = bindings.iter().map(|b| quote!(ref #b));
+            quote!(#path ( #(#pats),* ))
+        }
+        Fields::Unit => quote!(#path),
+    };
 
-    #[test]
-    fn struct_big() {
-        let t: BigStruct<i32> = random();
-        println!("{:?}", t);
-    }
+    let reconstruct = |values: &[TokenStream2]| -> TokenStream2 {
+        match *fields {
+            Fields::Named(ref named) => {
+                let fs = named.named.iter().zip(values).map(|(f, v)| {
+                    let name = &f.ident;
+                    quote!(#name: #v)
+                });
+                quote!(#path { #(#fs),* })
+            }
+            Fields::Unnamed(_) => {
+                let vs = values.iter();
+                quote!(#path ( #(#vs),* ))
+            }
+            Fields::Unit => quote!(#path),
+        }
+    };
+
+    let body = match bindings.len() {
+        0 => quote!(::quickcheck::empty_shrinker()),
+        1 => {
+            let b = &bindings[0];
+            let rebuilt = reconstruct(&[quote!(#b)]);
+            quote!(Box::new(#b.shrink().map(move |#b| #rebuilt)))
+        }
+        _ => {
+            // Chain one shrinker per field: vary field `i` through its own
+            // shrunk values while cloning every other field, so the body never
+            // relies on the tuple `Arbitrary` impl (which only exists up to
+            // arity 12). Each "other" field is captured as an owned clone and
+            // re-cloned per iteration, since the `map` closure is `FnMut` and
+            // must not move its captures out.
+            let chains = (0..bindings.len()).map(|i| {
+                let bi = &bindings[i];
+                let others = bindings
+                    .iter()
+                    .enumerate()
+                    .filter(|&(j, _)| j != i)
+                    .map(|(_, b)| quote!(let #b = #b.clone();));
+                let values: Vec<TokenStream2> = bindings
+                    .iter()
+                    .enumerate()
+                    .map(|(j, b)| if j == i { quote!(#b) } else { quote!(#b.clone()) })
+                    .collect();
+                let rebuilt = reconstruct(&values);
+                quote! {
+                    .chain({
+                        #(#others)*
+                        #bi.shrink().map(move |#bi| #rebuilt)
+                    })
+                }
+            });
+            quote! {
+                Box::new(::std::iter::empty::<Self>() #(#chains)*)
+            }
+        }
+    };
+
+    (pattern, body)
 }
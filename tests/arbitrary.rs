@@ -0,0 +1,49 @@
+#[macro_use]
+extern crate rand_derive;
+extern crate quickcheck;
+extern crate rand;
+
+use quickcheck::{Arbitrary, StdGen};
+
+#[derive(Arbitrary, Clone, Debug)]
+struct Unit;
+
+#[derive(Arbitrary, Clone, Debug)]
+struct Pair {
+    x: u8,
+    y: i32,
+}
+
+#[derive(Arbitrary, Clone, Debug)]
+struct Tup(u8, i32);
+
+#[derive(Arbitrary, Clone, Debug)]
+enum Mix {
+    A,
+    B(u8),
+    C { x: u8, y: u8 },
+}
+
+#[derive(Arbitrary, Clone, Debug)]
+struct Generic<T> {
+    x: T,
+    y: u8,
+}
+
+fn gen() -> StdGen<rand::ThreadRng> {
+    StdGen::new(rand::thread_rng(), 16)
+}
+
+#[test]
+fn arbitrary_and_shrink() {
+    let mut g = gen();
+    let _u = Unit::arbitrary(&mut g);
+    let p = Pair::arbitrary(&mut g);
+    let _: Vec<Pair> = p.shrink().take(4).collect();
+    let t = Tup::arbitrary(&mut g);
+    let _: Vec<Tup> = t.shrink().take(4).collect();
+    let m = Mix::arbitrary(&mut g);
+    let _: Vec<Mix> = m.shrink().take(4).collect();
+    let gen: Generic<u8> = Arbitrary::arbitrary(&mut g);
+    let _: Vec<Generic<u8>> = gen.shrink().take(4).collect();
+}
@@ -0,0 +1,220 @@
+#[macro_use]
+extern crate rand_derive;
+extern crate rand;
+
+use rand::random;
+
+#[derive(Rand, Debug)]
+enum Test {
+    A,
+    B,
+    C,
+}
+
+#[derive(Rand, Debug)]
+enum Test1 {
+    A,
+}
+
+#[derive(Rand, Debug)]
+enum Test2 {
+    A,
+    B,
+}
+
+#[test]
+fn it_works() {
+    let t: Test = random();
+    println!("{:?}", t);
+    let t1: Test1 = random();
+    println!("{:?}", t1);
+    let t2: Test2 = random();
+    println!("{:?}", t2);
+}
+
+#[derive(Rand, Debug)]
+enum Test3 {
+    A(i8),
+    B(Test2),
+}
+
+#[test]
+fn enum_tuplevar() {
+    let t: Test3 = random();
+    println!("{:?}", t);
+}
+
+#[derive(Rand, Debug)]
+enum TestS {
+    A { x: u8, y: u8 },
+    B { x: u8, y: u8, z: u8 },
+    C {},
+}
+
+#[test]
+fn enum_structvar() {
+    let t: TestS = random();
+    println!("{:?}", t);
+}
+
+#[derive(Rand, Debug)]
+enum TestGeneric1<T>
+where
+    T: ::rand::Rand,
+{
+    A { x: T },
+    B { x: u8, y: u8, z: u8 },
+}
+
+#[derive(Rand, Debug)]
+enum TestGeneric2<T> {
+    A { x: T },
+    B { x: u8, y: u8, z: u8 },
+}
+
+#[test]
+fn enum_generic() {
+    let t: TestGeneric1<TestS> = random();
+    println!("{:?}", t);
+    let s: TestGeneric2<()> = random();
+    println!("{:?}", s);
+}
+
+#[derive(Rand, Debug)]
+struct TestStruct;
+
+#[derive(Rand, Debug)]
+struct TestStruct2 {
+    x: u8,
+    y: (),
+}
+
+#[derive(Rand, Debug)]
+struct TestStruct3(u8, Test1);
+
+#[derive(Rand, Debug)]
+struct TestStruct4<T, U>
+where
+    T: 'static,
+{
+    x: T,
+    y: U,
+}
+
+#[test]
+fn struct_simple() {
+    let t: TestStruct = random();
+    println!("{:?}", t);
+    let s: TestStruct2 = random();
+    println!("{:?}", s);
+    let u: TestStruct3 = random();
+    println!("{:?}", u);
+    let v: TestStruct4<TestStruct, TestStruct2> = random();
+    println!("{:?}", v);
+}
+
+#[derive(Rand, Debug)]
+struct BigStruct<T> {
+    a: T,
+    b: (),
+    c: i32,
+    d: i32,
+    e: i32,
+    f: u8,
+    g: u8,
+    h: u8,
+    i: f32,
+    j: f32,
+    k: f32,
+    l: f32,
+    m: f32,
+    n: f64,
+    o: Test,
+    p: Test1,
+    q: TestStruct,
+    r: u8,
+    s: u8,
+    t: u8,
+    u: u8,
+    v: u8,
+    x: u8,
+    y: u8,
+    z: u8,
+}
+
+#[test]
+fn struct_big() {
+    let t: BigStruct<i32> = random();
+    println!("{:?}", t);
+}
+
+use std::marker::PhantomData;
+
+fn make_seven<R: rand::Rng>(_rng: &mut R) -> u8 {
+    7
+}
+
+#[derive(Rand, Debug)]
+struct WithAttrs<T> {
+    x: u8,
+    #[rand(skip)]
+    marker: PhantomData<T>,
+    #[rand(default)]
+    cache: u32,
+    #[rand(with = "make_seven")]
+    seven: u8,
+}
+
+#[test]
+fn field_attrs() {
+    let w: WithAttrs<i32> = random();
+    assert_eq!(w.cache, 0);
+    assert_eq!(w.seven, 7);
+    println!("{:?}", w);
+}
+
+trait MyTrait: rand::Rand {}
+impl MyTrait for u8 {}
+
+#[derive(Rand, Debug)]
+struct CustomBound<#[rand(bound = "T: MyTrait")] T> {
+    x: T,
+}
+
+#[test]
+fn custom_bound() {
+    let c: CustomBound<u8> = random();
+    println!("{:?}", c);
+}
+
+#[derive(Rand, Debug, PartialEq)]
+enum Weighted {
+    #[rand(weight = 0)]
+    Never,
+    #[rand(weight = 3)]
+    Often,
+    Sometimes,
+}
+
+#[test]
+fn weighted_enum() {
+    for _ in 0..1000 {
+        let w: Weighted = random();
+        assert!(w != Weighted::Never);
+    }
+}
+
+#[derive(Rand, Debug)]
+enum Discriminants {
+    A = 1,
+    B = 4,
+    C = 9,
+}
+
+#[test]
+fn explicit_discriminants() {
+    // Selection is uniform over the declared variants by parse order; the
+    // discriminant values do not influence the distribution.
+    let d: Discriminants = random();
+    println!("{:?}", d);
+}